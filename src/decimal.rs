@@ -0,0 +1,356 @@
+use std::{error, fmt};
+
+/// # Description
+/// Fixed-point decimal arithmetic for the compounding math.
+///
+/// Values are stored as an `i128` scaled by [`Decimal::SCALE`] (a "wad",
+/// borrowing the term from Solana token-lending's `math::Decimal`), and every
+/// operation is checked so an overflow or a division by (near) zero surfaces
+/// as a [`MathError`] instead of silently producing `inf`/`NaN` the way raw
+/// `f64` arithmetic does.
+///
+/// Multiplying (or rescaling, for division) two already-scaled values means
+/// the raw product briefly needs up to 256 bits before it's divided back down
+/// by [`Decimal::SCALE`] — an `i128 * i128` alone overflows once the unscaled
+/// product exceeds roughly 170. Solana's `Decimal` sidesteps this by backing
+/// its wad with a 192-bit integer; without a bigint crate available here,
+/// [`widening_mul_div`] does the same thing by hand, carrying the
+/// intermediate product through a 256-bit scratch value instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(i128);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathError {
+    /// an operation produced a value too large (or too small) to represent
+    Overflow,
+    /// a division was attempted where the divisor is zero (or close enough
+    /// to it that the result would not be meaningful)
+    DivideByZero,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathError::Overflow => write!(f, "math operation overflowed"),
+            MathError::DivideByZero => write!(f, "attempted to divide by (near) zero"),
+        }
+    }
+}
+
+impl error::Error for MathError {}
+
+/// an unsigned 256-bit integer, represented as two `u128` halves. Exists only
+/// as a scratch intermediate for [`widening_mul_div`]: just enough operations
+/// (widening multiply, bit test/set, shift, compare, subtract) to implement
+/// long division by a `u128` divisor.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct U256 {
+    hi: u128,
+    lo: u128,
+}
+
+impl U256 {
+    const ZERO: U256 = U256 { hi: 0, lo: 0 };
+
+    fn from_u128(value: u128) -> Self {
+        Self { hi: 0, lo: value }
+    }
+
+    /// full `u128 * u128 -> u256` widening multiply, done via 64-bit limbs
+    /// since Rust has no native 256-bit integer.
+    fn widening_mul(a: u128, b: u128) -> Self {
+        let a_lo = a & u64::MAX as u128;
+        let a_hi = a >> 64;
+        let b_lo = b & u64::MAX as u128;
+        let b_hi = b >> 64;
+
+        let lo_lo = a_lo * b_lo;
+        let lo_hi = a_lo * b_hi;
+        let hi_lo = a_hi * b_lo;
+        let hi_hi = a_hi * b_hi;
+
+        let mid = lo_hi + hi_lo + (lo_lo >> 64);
+        let lo = (lo_lo & u64::MAX as u128) | (mid << 64);
+        let hi = hi_hi + (mid >> 64);
+
+        Self { hi, lo }
+    }
+
+    fn bit(&self, index: u32) -> bool {
+        if index >= 128 {
+            (self.hi >> (index - 128)) & 1 == 1
+        } else {
+            (self.lo >> index) & 1 == 1
+        }
+    }
+
+    fn set_bit(&mut self, index: u32) {
+        if index >= 128 {
+            self.hi |= 1u128 << (index - 128);
+        } else {
+            self.lo |= 1u128 << index;
+        }
+    }
+
+    fn shl1(&mut self) {
+        let carry = self.lo >> 127;
+        self.hi = (self.hi << 1) | carry;
+        self.lo <<= 1;
+    }
+
+    fn ge(&self, rhs: &Self) -> bool {
+        self.hi > rhs.hi || (self.hi == rhs.hi && self.lo >= rhs.lo)
+    }
+
+    fn sub_assign(&mut self, rhs: &Self) {
+        let (lo, borrow) = self.lo.overflowing_sub(rhs.lo);
+        self.hi = self.hi.wrapping_sub(rhs.hi).wrapping_sub(borrow as u128);
+        self.lo = lo;
+    }
+
+    /// schoolbook long division of `self` by `divisor`, returning the
+    /// quotient if (and only if) it fits back into a `u128`
+    fn div_u128(&self, divisor: u128) -> Option<u128> {
+        if divisor == 0 {
+            return None;
+        }
+        let divisor = Self::from_u128(divisor);
+        let mut remainder = Self::ZERO;
+        let mut quotient = Self::ZERO;
+
+        for i in (0..256).rev() {
+            remainder.shl1();
+            if self.bit(i) {
+                remainder.lo |= 1;
+            }
+            if remainder.ge(&divisor) {
+                remainder.sub_assign(&divisor);
+                quotient.set_bit(i);
+            }
+        }
+
+        if quotient.hi == 0 {
+            Some(quotient.lo)
+        } else {
+            None
+        }
+    }
+}
+
+/// computes `(a * b) / divisor` for already-scaled wad magnitudes without
+/// ever requiring the intermediate product `a * b` to fit in 128 bits,
+/// returning `None` if the *final* result doesn't fit back into a `u128`.
+fn widening_mul_div(a: u128, b: u128, divisor: u128) -> Option<u128> {
+    U256::widening_mul(a, b).div_u128(divisor)
+}
+
+impl Decimal {
+    /// 1e18, the same wad scale Solana's token-lending `Decimal` uses
+    pub const SCALE: i128 = 1_000_000_000_000_000_000;
+
+    pub const fn zero() -> Self {
+        Self(0)
+    }
+
+    pub const fn one() -> Self {
+        Self(Self::SCALE)
+    }
+
+    pub fn from_f64(value: f64) -> Result<Self, MathError> {
+        if !value.is_finite() {
+            return Err(MathError::Overflow);
+        }
+        let scaled = value * Self::SCALE as f64;
+        if !scaled.is_finite() || scaled > i128::MAX as f64 || scaled < i128::MIN as f64 {
+            return Err(MathError::Overflow);
+        }
+        Ok(Self(scaled.round() as i128))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    pub fn try_add(self, rhs: Self) -> Result<Self, MathError> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Self)
+            .ok_or(MathError::Overflow)
+    }
+
+    pub fn try_sub(self, rhs: Self) -> Result<Self, MathError> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(Self)
+            .ok_or(MathError::Overflow)
+    }
+
+    /// multiplies two already-scaled values via a 256-bit intermediate
+    /// product, so this doesn't overflow until the *mathematical* result
+    /// itself no longer fits in a `Decimal` (see the module docs above).
+    pub fn try_mul(self, rhs: Self) -> Result<Self, MathError> {
+        let negative = (self.0 < 0) != (rhs.0 < 0);
+        let magnitude = widening_mul_div(
+            self.0.unsigned_abs(),
+            rhs.0.unsigned_abs(),
+            Self::SCALE as u128,
+        )
+        .ok_or(MathError::Overflow)?;
+        Self::from_magnitude(magnitude, negative)
+    }
+
+    pub fn try_div(self, rhs: Self) -> Result<Self, MathError> {
+        if rhs.0 == 0 {
+            return Err(MathError::DivideByZero);
+        }
+        let negative = (self.0 < 0) != (rhs.0 < 0);
+        let magnitude = widening_mul_div(
+            self.0.unsigned_abs(),
+            Self::SCALE as u128,
+            rhs.0.unsigned_abs(),
+        )
+        .ok_or(MathError::Overflow)?;
+        Self::from_magnitude(magnitude, negative)
+    }
+
+    fn from_magnitude(magnitude: u128, negative: bool) -> Result<Self, MathError> {
+        if negative {
+            // `i128::MIN`'s magnitude (2^127) doesn't fit in a positive
+            // `i128` (whose max is 2^127 - 1), even though `i128::MIN`
+            // itself is representable, so it needs its own case rather than
+            // negating after a positive-range check
+            if magnitude == i128::MIN.unsigned_abs() {
+                return Ok(Self(i128::MIN));
+            }
+            i128::try_from(magnitude)
+                .ok()
+                .and_then(i128::checked_neg)
+                .map(Self)
+                .ok_or(MathError::Overflow)
+        } else {
+            i128::try_from(magnitude)
+                .map(Self)
+                .map_err(|_| MathError::Overflow)
+        }
+    }
+
+    /// raises `self` to an arbitrary real power, which is what the
+    /// compounding growth term `g.powf(years)` needs. There's no cheap,
+    /// purely fixed-point way to do this, so this round-trips through `f64`
+    /// and rejects the result the moment it stops being finite, which is
+    /// exactly the failure mode this type exists to catch.
+    pub fn try_powf(self, exponent: f64) -> Result<Self, MathError> {
+        Self::from_f64(self.to_f64().powf(exponent))
+    }
+
+    pub fn try_floor_u64(self) -> Result<u64, MathError> {
+        if self.0 < 0 {
+            return Err(MathError::Overflow);
+        }
+        u64::try_from(self.0 / Self::SCALE).map_err(|_| MathError::Overflow)
+    }
+
+    pub fn try_ceil_u64(self) -> Result<u64, MathError> {
+        if self.0 < 0 {
+            return Err(MathError::Overflow);
+        }
+        u64::try_from((self.0 + Self::SCALE - 1) / Self::SCALE).map_err(|_| MathError::Overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+        (a - b).abs() <= epsilon
+    }
+
+    #[test]
+    fn add_and_sub_round_trip() {
+        let a = Decimal::from_f64(1_234_567.89).unwrap();
+        let b = Decimal::from_f64(42.5).unwrap();
+        let sum = a.try_add(b).unwrap();
+        assert!(approx_eq(sum.to_f64(), 1_234_610.39, 1e-6));
+        assert!(approx_eq(
+            sum.try_sub(b).unwrap().to_f64(),
+            a.to_f64(),
+            1e-6
+        ));
+    }
+
+    #[test]
+    fn mul_handles_realistic_wallet_balances() {
+        // the reviewer's repro: a per-period fee multiplied by a
+        // `collections_per_year` near the top of the bisection search range
+        // used in `growth_and_fee_term`, which used to overflow on the very
+        // first evaluation
+        let collections_per_year = Decimal::from_f64(1_000_000_000.0).unwrap();
+        let avg_fee = Decimal::from_f64(0.001).unwrap();
+        let fee_per_period = collections_per_year.try_mul(avg_fee).unwrap();
+        assert!(approx_eq(fee_per_period.to_f64(), 1_000_000.0, 1e-3));
+    }
+
+    #[test]
+    fn mul_matches_float_for_wallet_sized_values() {
+        let a = Decimal::from_f64(1_500_000.0).unwrap(); // a realistic microAlgo balance
+        let b = Decimal::from_f64(1.069).unwrap();
+        let product = a.try_mul(b).unwrap();
+        assert!(approx_eq(product.to_f64(), 1_500_000.0 * 1.069, 1e-3));
+    }
+
+    #[test]
+    fn mul_overflows_when_true_overflow_is_unavoidable() {
+        let huge = Decimal::from_f64(1e17).unwrap();
+        assert_eq!(huge.try_mul(huge), Err(MathError::Overflow));
+    }
+
+    #[test]
+    fn div_matches_float_for_wallet_sized_values() {
+        let a = Decimal::from_f64(1_500_000.0).unwrap();
+        let b = Decimal::from_f64(3.0).unwrap();
+        let quotient = a.try_div(b).unwrap();
+        assert!(approx_eq(quotient.to_f64(), 500_000.0, 1e-3));
+    }
+
+    #[test]
+    fn div_handles_large_numerator_without_overflow() {
+        // the old implementation computed `self.0 * SCALE` directly, which
+        // overflows for a large `self` well before the final divide
+        let large = Decimal::from_f64(1_000_000_000.0).unwrap();
+        let divisor = Decimal::from_f64(2.0).unwrap();
+        let quotient = large.try_div(divisor).unwrap();
+        assert!(approx_eq(quotient.to_f64(), 500_000_000.0, 1e-3));
+    }
+
+    #[test]
+    fn div_by_zero_is_an_error() {
+        let a = Decimal::one();
+        assert_eq!(a.try_div(Decimal::zero()), Err(MathError::DivideByZero));
+    }
+
+    #[test]
+    fn mul_result_exactly_at_i128_min_is_representable() {
+        // `i128::MIN`'s magnitude (2^127) is one past `i128::MAX`, so the
+        // negative result that lands exactly on it needs its own case in
+        // `from_magnitude` rather than failing a positive-range check
+        let min = Decimal(i128::MIN);
+        let result = min.try_mul(Decimal::one()).unwrap();
+        assert_eq!(result, min);
+    }
+
+    #[test]
+    fn powf_matches_float_powf() {
+        let base = Decimal::from_f64(1.069).unwrap();
+        let result = base.try_powf(2.0).unwrap();
+        assert!(approx_eq(result.to_f64(), 1.069_f64.powf(2.0), 1e-6));
+    }
+
+    #[test]
+    fn negative_values_round_trip_through_mul_and_div() {
+        let a = Decimal::from_f64(-50.0).unwrap();
+        let b = Decimal::from_f64(2.0).unwrap();
+        assert!(approx_eq(a.try_mul(b).unwrap().to_f64(), -100.0, 1e-6));
+        assert!(approx_eq(a.try_div(b).unwrap().to_f64(), -25.0, 1e-6));
+    }
+}
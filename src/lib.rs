@@ -1,20 +1,32 @@
 use algo_rust_sdk::AlgodClient;
 use std::{error, error::Error, fmt};
 
+mod decimal;
+mod rate_curve;
+mod reserve;
+mod stable_price;
+pub use decimal::{Decimal, MathError};
+pub use rate_curve::RateCurve;
+pub use reserve::{spendable_principal, AccountHoldings};
+pub use stable_price::{PriceOracle, StablePriceModel};
+
 pub trait Evaluate1D {
     ///evaluate f(x)
-    fn eval(&self, x: f64) -> f64;
+    fn eval(&self, x: f64) -> Result<f64, MathError>;
 
     /// evaluate f'(x) , where `delta`->0+
     /// uses central differences to approximate derivative
-    fn first_derivative(&self, x: f64, delta: f64) -> f64 {
-        (self.eval(x + delta) - self.eval(x - delta)) / (2.0 * delta)
+    fn first_derivative(&self, x: f64, delta: f64) -> Result<f64, MathError> {
+        Ok((self.eval(x + delta)? - self.eval(x - delta)?) / (2.0 * delta))
     }
 
     /// evaluate f''(x), where `delta` -> 0+
     /// uses a finite difference approximation
-    fn second_derivative(&self, x: f64, delta: f64) -> f64 {
-        (self.eval(x + delta) - (2.0 * self.eval(x)) + self.eval(x - delta)) / (delta * delta)
+    fn second_derivative(&self, x: f64, delta: f64) -> Result<f64, MathError> {
+        Ok(
+            (self.eval(x + delta)? - (2.0 * self.eval(x)?) + self.eval(x - delta)?)
+                / (delta * delta),
+        )
     }
     /// # Description
     /// finds extrema(approximately) using newtons method
@@ -28,14 +40,20 @@ pub trait Evaluate1D {
         max_iters: usize,
         delta: f64,
         epsilon: f64,
-    ) -> Option<f64> {
+    ) -> Result<Option<f64>, MathError> {
         for _ in 0..max_iters {
-            x0 = x0 - (self.first_derivative(x0, delta) / self.second_derivative(x0, delta));
-            if self.first_derivative(x0, delta).abs() < epsilon {
-                return Some(x0);
+            let second = self.second_derivative(x0, delta)?;
+            // a near-zero second derivative means newton's step would
+            // divide by (near) zero, so bail instead of producing garbage
+            if second.abs() < f64::EPSILON {
+                return Err(MathError::DivideByZero);
+            }
+            x0 = x0 - (self.first_derivative(x0, delta)? / second);
+            if self.first_derivative(x0, delta)?.abs() < epsilon {
+                return Ok(Some(x0));
             }
         }
-        None
+        Ok(None)
     }
 
     /// # Description
@@ -50,32 +68,32 @@ pub trait Evaluate1D {
         max_iters: usize,
         delta: f64,
         epsilon: f64,
-    ) -> Option<f64> {
+    ) -> Result<Option<f64>, MathError> {
         for _ in 0..max_iters {
             let (l, u) = range;
             let mid = (u - l) * 0.5 + l;
-            let fl = self.first_derivative(l, delta);
-            let fm = self.first_derivative(mid, delta);
-            let fu = self.first_derivative(u, delta);
+            let fl = self.first_derivative(l, delta)?;
+            let fm = self.first_derivative(mid, delta)?;
+            let fu = self.first_derivative(u, delta)?;
             let is_fl_pos = fl > 0.;
             let is_fu_pos = fu > 0.;
             let is_fm_pos = fm > 0.;
 
             if is_fl_pos == is_fu_pos {
-                return None;
+                return Ok(None);
             } else if fm.abs() < epsilon {
-                return Some(mid);
+                return Ok(Some(mid));
             } else if is_fm_pos != is_fu_pos {
                 range = (mid, u);
             } else if is_fl_pos != is_fm_pos {
                 range = (l, mid);
             } else {
-                return None;
+                return Ok(None);
             }
         }
 
         let (l, u) = range;
-        Some((u - l) * 0.5 + l)
+        Ok(Some((u - l) * 0.5 + l))
     }
 }
 
@@ -90,7 +108,7 @@ pub struct Function1DAnalytic<FuncType, CoefsType> {
 
 impl<FuncType, CoefsType> Function1DAnalytic<FuncType, CoefsType>
 where
-    FuncType: Fn(f64, CoefsType) -> f64 + Copy,
+    FuncType: Fn(f64, CoefsType) -> Result<f64, MathError> + Copy,
     CoefsType: Coefs + Copy,
 {
     ///define and create f(x)
@@ -102,15 +120,30 @@ where
 #[derive(Copy, Clone)]
 pub struct CompoundModelCoefs {
     pub years: f64,
-    pub rate: f64,
+    pub rate_curve: RateCurve,
     pub avg_fees: f64,
     pub initial_principal: f64,
 }
 impl CompoundModelCoefs {
+    /// convenience constructor for the common case of a single constant rate
     pub fn new(years: f64, rate: f64, avg_fees: f64, initial_principal: f64) -> Self {
+        Self::with_rate_curve(
+            years,
+            RateCurve::Constant(rate),
+            avg_fees,
+            initial_principal,
+        )
+    }
+
+    pub fn with_rate_curve(
+        years: f64,
+        rate_curve: RateCurve,
+        avg_fees: f64,
+        initial_principal: f64,
+    ) -> Self {
         Self {
             years,
-            rate,
+            rate_curve,
             avg_fees,
             initial_principal,
         }
@@ -120,7 +153,10 @@ impl CompoundModelCoefs {
 impl Coefs for CompoundModelCoefs {}
 
 pub struct AlgoInterestModel {
-    model: Function1DAnalytic<fn(f64, CompoundModelCoefs) -> f64, CompoundModelCoefs>,
+    model: Function1DAnalytic<
+        fn(f64, CompoundModelCoefs) -> Result<f64, MathError>,
+        CompoundModelCoefs,
+    >,
 }
 impl AlgoInterestModel {
     pub fn new(coefs: CompoundModelCoefs) -> Self {
@@ -131,11 +167,39 @@ impl AlgoInterestModel {
 
     /// # Description
     /// returns the optimal number of seconds you should wait before collecting the reward
-    pub fn get_ideal_reward_wait_time(&self) -> Option<f64> {
-        self.search_extrema_bisection((1.0, 1000000000.), 64, 0.0001, 0.0000001)
+    pub fn get_ideal_reward_wait_time(&self) -> Result<Option<f64>, MathError> {
+        Ok(self
+            .search_extrema_bisection((1.0, 1000000000.), 64, 0.0001, 0.0000001)?
             .map(|optimal_collections_per_year| {
                 (365.0 / optimal_collections_per_year) * 24.0 * 3600.
-            })
+            }))
+    }
+
+    /// # Description
+    /// like [`Self::get_ideal_reward_wait_time`], but optimizes for the
+    /// wallet's fiat value (e.g. USD) at a given `stable_price`, typically
+    /// the smoothed price from a [`StablePriceModel`], rather than its raw
+    /// ALGO value.
+    /// # Comments
+    /// the optimum isn't just the ALGO-optimal interval: fees are paid in
+    /// ALGO, not fiat, so the fee drag below stays denominated in ALGO while
+    /// the compounding principal is converted through `stable_price`. that
+    /// asymmetry is what moves the optimal collection interval as price
+    /// moves, unlike uniformly scaling the whole ALGO-denominated curve
+    /// (which wouldn't change where its extrema are).
+    pub fn get_ideal_reward_wait_time_fiat(
+        &self,
+        stable_price: f64,
+    ) -> Result<Option<f64>, MathError> {
+        let fiat_model = FiatInterestModel {
+            coefs: self.model.coefs,
+            stable_price,
+        };
+        Ok(fiat_model
+            .search_extrema_bisection((1.0, 1000000000.), 64, 0.0001, 0.0000001)?
+            .map(|optimal_collections_per_year| {
+                (365.0 / optimal_collections_per_year) * 24.0 * 3600.
+            }))
     }
 
     /// # Description
@@ -146,49 +210,219 @@ impl AlgoInterestModel {
     /// First i came up with a recurrence relation for compounding:
     /// ```
     ///  C(0) = A
-    ///  C(n) = C(n-1)*(1 + r/t )^t - f*t
+    ///  C(n) = C(n-1)*(1 + r_n/t )^t - f*t
     /// ```
     /// * `A` - is principal
-    /// * `r` - is interest rate per year
+    /// * `r_n` - is the interest rate realized during year `n`, from `coefs.rate_curve`
     /// * `t` - is number of time you collect rewards per year
     /// * `f` - is average fee per collection
     /// * `n` - is the years of compounding \
-    /// Solving the recurrence relation yields a closed-form equation, which you can then use to find the optimal 't'.
-    /// I use simple numerical approximations for finding the local extrema of the function  
-    fn projected_wallet_price(collections_per_year: f64, coefs: CompoundModelCoefs) -> f64 {
-        // 'g' is a sub expression in the complete formula that appears multiple times.
-        // I have no meaningful name to give it
-        let g = (coefs.rate / collections_per_year + 1.0).powf(collections_per_year);
-        coefs.initial_principal * g.powf(coefs.years)
-            - ((collections_per_year * coefs.avg_fees) * (g.powf(coefs.years) - 1.0)) / (g - 1.0)
+    /// When `rate_curve` is constant this reduces to the original closed-form
+    /// geometric series; for a curve that varies per year there's no closed
+    /// form, so [`Self::growth_and_fee_term`] below just runs the recurrence
+    /// directly, one year at a time, consuming `r_n` from the curve at each
+    /// step. I use simple numerical approximations for finding the local
+    /// extrema of the resulting function.
+    ///
+    /// All arithmetic runs through [`Decimal`] so an overflow in `g.powf(t)`
+    /// comes back as a [`MathError`] instead of silently turning into
+    /// `inf`/`NaN`.
+    fn projected_wallet_price(
+        collections_per_year: f64,
+        coefs: CompoundModelCoefs,
+    ) -> Result<f64, MathError> {
+        let (growth, fee_drag) = Self::growth_and_fee_term(collections_per_year, coefs)?;
+        let principal = Decimal::from_f64(coefs.initial_principal)?;
+        Ok(principal.try_mul(growth)?.try_sub(fee_drag)?.to_f64())
+    }
+
+    /// runs the recurrence relation documented above one year at a time,
+    /// pulling `r_n` from `coefs.rate_curve`, and returns the cumulative
+    /// growth multiplier `G_n = g_1*g_2*...*g_n` alongside the cumulative
+    /// fee drag `F_n` (defined by `F_0 = 0`, `F_n = F_{n-1}*g_n + f*t`) so
+    /// that `principal*G_n - F_n == C(n)`. Both [`Self::projected_wallet_price`]
+    /// and [`FiatInterestModel`] build their wallet-price functions on top of
+    /// this, the latter converting only the growth side through a price.
+    fn growth_and_fee_term(
+        collections_per_year: f64,
+        coefs: CompoundModelCoefs,
+    ) -> Result<(Decimal, Decimal), MathError> {
+        if collections_per_year <= 0.0 {
+            return Err(MathError::DivideByZero);
+        }
+
+        let one = Decimal::one();
+        let t = Decimal::from_f64(collections_per_year)?;
+        let fees = Decimal::from_f64(coefs.avg_fees)?;
+        let fee_per_period = t.try_mul(fees)?;
+
+        let mut growth = one;
+        let mut fee_drag = Decimal::zero();
+
+        let whole_years = coefs.years.floor().max(0.0) as u64;
+        for n in 0..whole_years {
+            let rate = Decimal::from_f64(coefs.rate_curve.rate_at(n as f64))?;
+            let g = one
+                .try_add(rate.try_div(t)?)?
+                .try_powf(collections_per_year)?;
+            growth = growth.try_mul(g)?;
+            fee_drag = fee_drag.try_mul(g)?.try_add(fee_per_period)?;
+        }
+
+        // a fractional final year compounds (and pays fees) for only that
+        // fraction of the usual period
+        let fractional_year = coefs.years - whole_years as f64;
+        if fractional_year > 0.0 {
+            let rate = Decimal::from_f64(coefs.rate_curve.rate_at(whole_years as f64))?;
+            let g = one
+                .try_add(rate.try_div(t)?)?
+                .try_powf(collections_per_year * fractional_year)?;
+            let partial_fee = fee_per_period.try_mul(Decimal::from_f64(fractional_year)?)?;
+            growth = growth.try_mul(g)?;
+            fee_drag = fee_drag.try_mul(g)?.try_add(partial_fee)?;
+        }
+
+        Ok((growth, fee_drag))
     }
 }
 
 impl Evaluate1D for AlgoInterestModel {
-    fn eval(&self, x: f64) -> f64 {
+    fn eval(&self, x: f64) -> Result<f64, MathError> {
         (self.model.func)(x, self.model.coefs)
     }
 }
 
-#[derive(Debug)]
-pub struct ConfirmationError {
-    msg: String,
+/// # Description
+/// the fiat-value counterpart to [`AlgoInterestModel`]: projects the wallet's
+/// value in fiat terms (principal converted at `stable_price`) while keeping
+/// the fee drag denominated in ALGO, since that's the unit fees are actually
+/// paid in. See [`AlgoInterestModel::get_ideal_reward_wait_time_fiat`].
+struct FiatInterestModel {
+    coefs: CompoundModelCoefs,
+    stable_price: f64,
 }
 
-impl ConfirmationError {
-    pub fn new(msg: String) -> Box<Self> {
-        Box::new(Self { msg })
+impl Evaluate1D for FiatInterestModel {
+    fn eval(&self, x: f64) -> Result<f64, MathError> {
+        let (growth, fee_term) = AlgoInterestModel::growth_and_fee_term(x, self.coefs)?;
+        let principal = Decimal::from_f64(self.coefs.initial_principal)?;
+        let price = Decimal::from_f64(self.stable_price)?;
+        let compounded_fiat = principal.try_mul(growth)?.try_mul(price)?;
+        Ok(compounded_fiat.try_sub(fee_term)?.to_f64())
     }
 }
 
+/// # Description
+/// why [`Self`]'s confirmation/retry loop in `main` gave up, distinguishing
+/// the cases that call for different follow-up action:
+/// * a transaction whose valid round window lapsed gets rebuilt, re-signed
+///   and rebroadcast automatically, so [`ConfirmationError::ExpiredResubmitted`]
+///   just means "there's a fresh one in flight, keep going"
+/// * [`ConfirmationError::RejectedPermanent`] means the pool rejected the
+///   final attempt, with no retry budget left to rebuild and resubmit again;
+///   earlier attempts may simply have timed out rather than been rejected
+///   too, so this isn't a guarantee every attempt saw a rejection
+/// * [`ConfirmationError::TimeoutExhausted`] means every attempt ran out its
+///   polling window without ever observing a confirmation or a rejection
+/// * [`ConfirmationError::ReserveExhausted`] means a retry was skipped
+///   outright because bumping the fee again would have dropped the account
+///   under its minimum-balance reserve
+#[derive(Debug)]
+pub enum ConfirmationError {
+    /// the original transaction's round window expired; it has been rebuilt
+    /// with a fresh window, re-signed, and resubmitted as `new_tx_id`, but
+    /// there was no more retry budget left to also wait on that resubmission
+    ExpiredResubmitted { new_tx_id: String },
+    /// the node's transaction pool rejected the last attempt, with no retry
+    /// budget left to rebuild and resubmit again
+    RejectedPermanent { pool_error: String },
+    /// every retry attempt polled without ever observing a confirmation or a
+    /// pool rejection
+    TimeoutExhausted { attempts: u32 },
+    /// the next fee-bumped rebuild would have dropped the account below its
+    /// minimum balance plus safety margin, so no further retries were sent
+    ReserveExhausted { projected_post_fee_balance: u64 },
+}
+
 impl fmt::Display for ConfirmationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.msg)
+        match self {
+            ConfirmationError::ExpiredResubmitted { new_tx_id } => write!(
+                f,
+                "transaction expired and was resubmitted as {}",
+                new_tx_id
+            ),
+            ConfirmationError::RejectedPermanent { pool_error } => {
+                write!(f, "transaction permanently rejected: {}", pool_error)
+            }
+            ConfirmationError::TimeoutExhausted { attempts } => {
+                write!(
+                    f,
+                    "timed out after exhausting {} retry attempt(s)",
+                    attempts
+                )
+            }
+            ConfirmationError::ReserveExhausted {
+                projected_post_fee_balance,
+            } => write!(
+                f,
+                "stopped retrying: next fee bump would drop balance to {} microAlgos, under reserve",
+                projected_post_fee_balance
+            ),
+        }
     }
 }
 
 impl error::Error for ConfirmationError {}
 
+/// # Description
+/// configures the rebroadcast/retry behaviour of `confirm_transaction`: how
+/// many times to rebuild and resubmit an unconfirmed transaction, and how
+/// long to back off between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    /// total number of confirmation attempts, including the first one
+    pub max_attempts: u32,
+    /// how many rounds to wait for confirmation before giving up on an attempt
+    pub rounds_per_attempt: u64,
+    /// delay before the first retry; grows by `backoff_multiplier` each attempt
+    pub initial_backoff: std::time::Duration,
+    pub backoff_multiplier: f64,
+    /// added to the fee on every rebuild, so a fee-starved transaction has a
+    /// better chance of making it into the pool on the next attempt
+    pub fee_bump: u64,
+}
+
+impl RetryConfig {
+    pub fn new(
+        max_attempts: u32,
+        rounds_per_attempt: u64,
+        initial_backoff: std::time::Duration,
+        backoff_multiplier: f64,
+        fee_bump: u64,
+    ) -> Self {
+        Self {
+            max_attempts,
+            rounds_per_attempt,
+            initial_backoff,
+            backoff_multiplier,
+            fee_bump,
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            rounds_per_attempt: 10,
+            initial_backoff: std::time::Duration::from_secs(2),
+            backoff_multiplier: 2.0,
+            fee_bump: 1000,
+        }
+    }
+}
+
 pub fn print_algod_status(algod_client: &AlgodClient) -> Result<(), Box<dyn Error>> {
     let node_status = algod_client.status()?;
     println!("algod last round: {}", node_status.last_round);
@@ -200,3 +434,56 @@ pub fn print_algod_status(algod_client: &AlgodClient) -> Result<(), Box<dyn Erro
     println!("algod latest version: {}", node_status.last_version);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+        (a - b).abs() <= epsilon
+    }
+
+    /// `growth_and_fee_term` is a generalization of the original closed-form
+    /// geometric series for a constant rate: `G_n = g^n` and
+    /// `F_n = f*t*(g^n - 1)/(g - 1)`, where `g = (1 + r/t)^t`. This pins the
+    /// recurrence-based implementation against that closed form for a whole
+    /// number of years, where the two must agree exactly.
+    #[test]
+    fn growth_and_fee_term_matches_closed_form_for_constant_rate() {
+        let years = 3.0;
+        let rate = 0.069;
+        let t = 12.0;
+        let fee = 0.01;
+        let coefs = CompoundModelCoefs::new(years, rate, fee, 1000.0);
+
+        let (growth, fee_drag) =
+            AlgoInterestModel::growth_and_fee_term(t, coefs).expect("growth_and_fee_term failed");
+
+        let g = (1.0 + rate / t).powf(t);
+        let expected_growth = g.powf(years);
+        let expected_fee_drag = fee * t * (g.powf(years) - 1.0) / (g - 1.0);
+
+        assert!(approx_eq(growth.to_f64(), expected_growth, 1e-6));
+        assert!(approx_eq(fee_drag.to_f64(), expected_fee_drag, 1e-3));
+    }
+
+    #[test]
+    fn projected_wallet_price_matches_closed_form_for_constant_rate() {
+        let years = 2.0;
+        let rate = 0.05;
+        let t = 4.0;
+        let fee = 0.02;
+        let principal = 500.0;
+        let coefs = CompoundModelCoefs::new(years, rate, fee, principal);
+        let model = AlgoInterestModel::new(coefs);
+
+        let price = model.eval(t).expect("eval failed");
+
+        let g = (1.0 + rate / t).powf(t);
+        let expected_growth = g.powf(years);
+        let expected_fee_drag = fee * t * (g.powf(years) - 1.0) / (g - 1.0);
+        let expected_price = principal * expected_growth - expected_fee_drag;
+
+        assert!(approx_eq(price, expected_price, 1e-2));
+    }
+}
@@ -3,12 +3,11 @@ use std::{
     error::Error,
     fs,
     io::{self},
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use algo_rust_sdk::{
     account::Account,
-    algod::models::TransactionID,
     transaction::{BaseTransaction, Payment, Transaction, TransactionType},
     AlgodClient, MicroAlgos, Round,
 };
@@ -25,6 +24,36 @@ pub fn get_algod_address_token_pair() -> Result<(String, String), Box<dyn Error>
     Ok((algod_address, algod_token))
 }
 
+/// fetches the ALGO/USD spot price from the endpoint named by the
+/// `ALGO_PRICE_ORACLE_URL` environment variable. the endpoint is expected to
+/// return a JSON body with a top-level numeric `"price"` field.
+struct HttpPriceOracle {
+    url: String,
+}
+
+impl PriceOracle for HttpPriceOracle {
+    fn fetch_spot_price(&self) -> Result<f64, Box<dyn Error>> {
+        let body = reqwest::blocking::get(&self.url)?.text()?;
+        let key_pos = body
+            .find("\"price\"")
+            .ok_or_else(|| format!("no \"price\" field in {}", body))?;
+        let value_start = key_pos
+            + body[key_pos..]
+                .find(':')
+                .ok_or_else(|| format!("malformed \"price\" field in {}", body))?
+            + 1;
+        let value_end = body[value_start..]
+            .find(|c: char| c == ',' || c == '}')
+            .map(|offset| value_start + offset)
+            .unwrap_or(body.len());
+        Ok(body[value_start..value_end].trim().parse::<f64>()?)
+    }
+}
+
+fn current_unix_timestamp() -> Result<u64, Box<dyn Error>> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let mut auto_payment_count = 0;
 
@@ -39,20 +68,107 @@ fn main() -> Result<(), Box<dyn Error>> {
     let bank_acc = Account::from_mnemonic(account_mnemonic.trim())?;
     let bank_addr = bank_acc.address();
 
+    // the oracle is optional: without it we fall back to optimizing in raw
+    // ALGO terms, same as before this feature existed
+    let price_oracle = env::var("ALGO_PRICE_ORACLE_URL")
+        .ok()
+        .map(|url| HttpPriceOracle { url });
+    let mut stable_price_model = match &price_oracle {
+        Some(oracle) => Some(StablePriceModel::new(
+            oracle.fetch_spot_price()?,
+            current_unix_timestamp()?,
+            60,
+            0.0005,
+            0.0005,
+        )),
+        None => None,
+    };
+
     loop {
         let transaction_params = algod_client.transaction_params()?;
         let genesis_id = transaction_params.genesis_id;
         let genesis_hash = transaction_params.genesis_hash;
         let acc_info = algod_client.account_information(&bank_addr.encode_string())?;
 
-        let balance = acc_info.amount.0 as f64 / 1E6;
-        println!("current algo = {}", balance);
+        // the network's suggested fee reflects real congestion; a 0 reading
+        // means "use the minimum", same as before this was threaded through
+        let suggested_fee = if transaction_params.fee.0 > 0 {
+            transaction_params.fee
+        } else {
+            MicroAlgos(1000)
+        };
 
-        let model = AlgoInterestModel::new(CompoundModelCoefs::new(1., 0.069, 0.001, balance));
+        let holdings = AccountHoldings {
+            num_assets_held: acc_info.assets.len() as u64,
+            num_apps_opted_in: acc_info.apps_local_state.len() as u64,
+            num_apps_created: acc_info.created_apps.len() as u64,
+            created_apps_uint_schema: acc_info
+                .created_apps
+                .iter()
+                .map(|app| app.params.global_state_schema.num_uint)
+                .sum(),
+            created_apps_byte_slice_schema: acc_info
+                .created_apps
+                .iter()
+                .map(|app| app.params.global_state_schema.num_byte_slice)
+                .sum(),
+        };
+        let min_balance = holdings.min_balance_micro_algos();
+        // a small cushion beyond the network-enforced minimum so the next
+        // cycle's fee doesn't itself dip the account below reserve
+        let safety_margin = 100_000;
 
-        let delay = match model.get_ideal_reward_wait_time() {
-            Some(seconds) => seconds,
-            None => 3600. * 24., // if for some reason there is an error, it just waits a dat
+        let spendable = match spendable_principal(acc_info.amount.0, min_balance, safety_margin) {
+            Some(spendable) => spendable,
+            None => {
+                println!(
+                    "balance {} microAlgos is at or under the {} microAlgo reserve (min balance + safety margin); skipping this cycle",
+                    acc_info.amount.0,
+                    min_balance + safety_margin
+                );
+                std::thread::sleep(Duration::from_secs(3600));
+                continue;
+            }
+        };
+
+        let balance = spendable as f64 / 1E6;
+        println!(
+            "current algo = {} (reserve = {} microAlgos)",
+            balance,
+            min_balance + safety_margin
+        );
+
+        let model = AlgoInterestModel::new(CompoundModelCoefs::new(
+            1.,
+            0.069,
+            suggested_fee.0 as f64 / 1E6,
+            balance,
+        ));
+
+        let delay = if let (Some(oracle), Some(stable_model)) =
+            (&price_oracle, &mut stable_price_model)
+        {
+            match oracle.fetch_spot_price() {
+                Ok(spot_price) => stable_model.update(spot_price, current_unix_timestamp()?),
+                Err(err) => println!("failed to fetch spot price:{}, reusing stable price", err),
+            }
+            match model.get_ideal_reward_wait_time_fiat(stable_model.stable_price) {
+                Ok(Some(seconds)) => seconds,
+                Ok(None) => 3600. * 24.,
+                Err(err) => {
+                    println!("failed to compute ideal wait time:{}, waiting a day", err);
+                    3600. * 24.
+                }
+            }
+        } else {
+            match model.get_ideal_reward_wait_time() {
+                Ok(Some(seconds)) => seconds,
+                Ok(None) => 3600. * 24., // if for some reason there is an error, it just waits a dat
+                Err(err) => {
+                    println!("failed to compute ideal wait time:{}, waiting a day", err);
+                    3600. * 24.
+                }
+            }
         };
 
         let base_transaction = BaseTransaction {
@@ -77,10 +193,27 @@ fn main() -> Result<(), Box<dyn Error>> {
             close_remainder_to: None,
         };
 
+        let fee = suggested_fee;
+
+        // broadcasting costs `fee` microAlgos out of the account's real
+        // (unreserved) balance, so refuse to send if that would leave the
+        // account under reserve rather than letting the network reject it
+        if let Some(projected_post_fee_balance) =
+            would_breach_reserve(acc_info.amount.0, fee, min_balance, safety_margin)
+        {
+            println!(
+                "sending would drop balance to {} microAlgos, under the {} microAlgo reserve; skipping this cycle",
+                projected_post_fee_balance,
+                min_balance + safety_margin
+            );
+            std::thread::sleep(Duration::from_secs(3600));
+            continue;
+        }
+
         let transaction = Transaction::new_flat_fee(
-            base_transaction,
-            MicroAlgos(1000),
-            TransactionType::Payment(payment),
+            base_transaction.clone(),
+            fee,
+            TransactionType::Payment(payment.clone()),
         );
 
         let signed_transaction = bank_acc.sign_transaction(&transaction)?;
@@ -91,7 +224,17 @@ fn main() -> Result<(), Box<dyn Error>> {
         let send_response = algod_client.send_transaction(&signed_transaction)?;
         println!("Transaction ID: {}", send_response.tx_id);
 
-        match confirm_transaction(&algod_client, &send_response, 10) {
+        match confirm_transaction(
+            &algod_client,
+            &bank_acc,
+            base_transaction,
+            payment,
+            fee,
+            send_response.tx_id,
+            min_balance,
+            safety_margin,
+            &RetryConfig::default(),
+        ) {
             Ok(_) => {
                 println!(
                     "Transaction success, sleeping by {} seconds or {} days",
@@ -101,37 +244,215 @@ fn main() -> Result<(), Box<dyn Error>> {
                 std::thread::sleep(Duration::from_secs_f64(delay));
                 auto_payment_count += 1;
             }
-            Err(kind) => println!("Transactin failed:{}", kind),
+            Err(err @ ConfirmationError::RejectedPermanent { .. }) => return Err(err.into()),
+            Err(kind) => println!(
+                "Transaction not confirmed yet:{}, retrying next cycle",
+                kind
+            ),
         }
     }
 
     Ok(())
 }
 
-pub fn confirm_transaction(
+/// what a single polling window observed about a submitted transaction
+enum PollOutcome {
+    Confirmed,
+    Rejected(String),
+    TimedOut,
+}
+
+fn poll_for_confirmation(
     algod_client: &AlgodClient,
-    send_response: &TransactionID,
-    timeout: u64,
-) -> Result<(), Box<dyn Error>> {
+    tx_id: &str,
+    rounds: u64,
+) -> Result<PollOutcome, Box<dyn Error>> {
     let status = algod_client.status()?;
     let start_round: Round = status.last_round + 1;
     let mut current_round: Round = start_round;
 
-    while current_round.0 < (start_round + timeout).0 {
-        let pending_info = algod_client.pending_transaction_information(&send_response.tx_id)?;
+    while current_round.0 < (start_round + rounds).0 {
+        let pending_info = algod_client.pending_transaction_information(tx_id)?;
         if pending_info.round.is_some() && pending_info.round.unwrap() > 0 {
-            return Ok(());
-        } else {
-            if pending_info.pool_error.len() > 0 {
-                return Err(ConfirmationError::new(format!(
-                    "Transaction Rejected:{}",
-                    pending_info.pool_error
-                )));
-            }
+            return Ok(PollOutcome::Confirmed);
+        }
+        if pending_info.pool_error.len() > 0 {
+            return Ok(PollOutcome::Rejected(pending_info.pool_error));
         }
         algod_client.status_after_block(current_round)?;
         current_round = current_round + 1;
     }
 
-    Err(ConfirmationError::new(String::from("Timeout exceeded")))
+    Ok(PollOutcome::TimedOut)
+}
+
+/// returns the projected post-fee balance if paying `fee` out of
+/// `balance_micro_algos` would drop the account under `min_balance_micro_algos`
+/// plus `safety_margin_micro_algos`, or `None` if the reserve would hold.
+fn would_breach_reserve(
+    balance_micro_algos: u64,
+    fee: MicroAlgos,
+    min_balance_micro_algos: u64,
+    safety_margin_micro_algos: u64,
+) -> Option<u64> {
+    let projected_post_fee_balance = balance_micro_algos.saturating_sub(fee.0);
+    if projected_post_fee_balance < min_balance_micro_algos + safety_margin_micro_algos {
+        Some(projected_post_fee_balance)
+    } else {
+        None
+    }
+}
+
+/// re-fetches `bank_acc`'s balance and checks it against reserve as though
+/// `next_fee` were about to be paid, so a retry's fee bump can't silently
+/// push the account under reserve the way only the very first send used to
+/// be guarded against.
+fn ensure_reserve_not_breached(
+    algod_client: &AlgodClient,
+    bank_acc: &Account,
+    next_fee: MicroAlgos,
+    min_balance: u64,
+    safety_margin: u64,
+    attempt: u32,
+) -> Result<(), ConfirmationError> {
+    let acc_info = algod_client
+        .account_information(&bank_acc.address().encode_string())
+        .map_err(|_| ConfirmationError::TimeoutExhausted { attempts: attempt })?;
+    if let Some(projected_post_fee_balance) =
+        would_breach_reserve(acc_info.amount.0, next_fee, min_balance, safety_margin)
+    {
+        return Err(ConfirmationError::ReserveExhausted {
+            projected_post_fee_balance,
+        });
+    }
+    Ok(())
+}
+
+/// rebuilds `base_transaction` with a fresh `first_valid`/`last_valid`
+/// window and a bumped fee, re-signs it, and rebroadcasts, returning the new
+/// transaction's id.
+fn rebuild_and_resubmit(
+    algod_client: &AlgodClient,
+    bank_acc: &Account,
+    base_transaction: &mut BaseTransaction,
+    payment: &Payment,
+    fee: &mut MicroAlgos,
+    fee_bump: u64,
+) -> Result<String, Box<dyn Error>> {
+    let transaction_params = algod_client.transaction_params()?;
+    base_transaction.first_valid = transaction_params.last_round;
+    base_transaction.last_valid = transaction_params.last_round + 1000;
+    *fee = MicroAlgos(fee.0 + fee_bump);
+
+    let transaction = Transaction::new_flat_fee(
+        base_transaction.clone(),
+        *fee,
+        TransactionType::Payment(payment.clone()),
+    );
+    let signed_transaction = bank_acc.sign_transaction(&transaction)?;
+    let send_response = algod_client.send_transaction(&signed_transaction)?;
+    Ok(send_response.tx_id)
+}
+
+/// # Description
+/// waits for a submitted transaction to confirm, rebuilding it with a fresh
+/// `first_valid`/`last_valid` window (and a bumped fee) and rebroadcasting
+/// on every expired or rejected attempt, up to `retry.max_attempts` times
+/// with exponential backoff between attempts. Before each rebuild, re-checks
+/// the account's balance against `min_balance`/`safety_margin` the same way
+/// the first send was gated, since a bumped fee can push the account under
+/// reserve across enough retries even when the first send was safely clear.
+pub fn confirm_transaction(
+    algod_client: &AlgodClient,
+    bank_acc: &Account,
+    mut base_transaction: BaseTransaction,
+    payment: Payment,
+    mut fee: MicroAlgos,
+    mut tx_id: String,
+    min_balance: u64,
+    safety_margin: u64,
+    retry: &RetryConfig,
+) -> Result<(), ConfirmationError> {
+    let mut backoff = retry.initial_backoff;
+
+    for attempt in 1..=retry.max_attempts {
+        // a transport/RPC hiccup while polling isn't a verdict on the
+        // transaction itself, so treat it the same as a plain timeout rather
+        // than bailing out of the retry loop on the first flaky response
+        let outcome = match poll_for_confirmation(algod_client, &tx_id, retry.rounds_per_attempt) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                println!(
+                    "attempt {} failed to poll for confirmation:{}, treating as a timeout",
+                    attempt, err
+                );
+                PollOutcome::TimedOut
+            }
+        };
+
+        match outcome {
+            PollOutcome::Confirmed => return Ok(()),
+            PollOutcome::Rejected(pool_error) => {
+                if attempt == retry.max_attempts {
+                    return Err(ConfirmationError::RejectedPermanent { pool_error });
+                }
+                println!(
+                    "attempt {} rejected:{}, rebuilding and rebroadcasting",
+                    attempt, pool_error
+                );
+            }
+            PollOutcome::TimedOut => {
+                if attempt == retry.max_attempts {
+                    // out of polling budget, but still worth rebuilding and
+                    // rebroadcasting once more so there's a fresh
+                    // transaction in flight rather than abandoning the
+                    // expired one outright
+                    ensure_reserve_not_breached(
+                        algod_client,
+                        bank_acc,
+                        MicroAlgos(fee.0 + retry.fee_bump),
+                        min_balance,
+                        safety_margin,
+                        attempt,
+                    )?;
+                    let new_tx_id = rebuild_and_resubmit(
+                        algod_client,
+                        bank_acc,
+                        &mut base_transaction,
+                        &payment,
+                        &mut fee,
+                        retry.fee_bump,
+                    )
+                    .map_err(|_| ConfirmationError::TimeoutExhausted { attempts: attempt })?;
+                    return Err(ConfirmationError::ExpiredResubmitted { new_tx_id });
+                }
+                println!("attempt {} expired, rebuilding and rebroadcasting", attempt);
+            }
+        }
+
+        std::thread::sleep(backoff);
+        backoff = backoff.mul_f64(retry.backoff_multiplier);
+
+        ensure_reserve_not_breached(
+            algod_client,
+            bank_acc,
+            MicroAlgos(fee.0 + retry.fee_bump),
+            min_balance,
+            safety_margin,
+            attempt,
+        )?;
+        tx_id = rebuild_and_resubmit(
+            algod_client,
+            bank_acc,
+            &mut base_transaction,
+            &payment,
+            &mut fee,
+            retry.fee_bump,
+        )
+        .map_err(|_| ConfirmationError::TimeoutExhausted { attempts: attempt })?;
+    }
+
+    Err(ConfirmationError::TimeoutExhausted {
+        attempts: retry.max_attempts,
+    })
 }
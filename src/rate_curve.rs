@@ -0,0 +1,118 @@
+/// # Description
+/// supplies the interest rate that applies at a given point in the
+/// compounding horizon, so [`crate::AlgoInterestModel`] doesn't have to
+/// assume a single constant rate for the whole projection. Borrows the
+/// cumulative-rate idea from Solana token-lending's
+/// `Obligation::cumulative_borrow_rate_wads`: instead of integrating a
+/// running rate ourselves, the model asks the curve for the rate realized at
+/// each step and folds it into the recurrence directly.
+///
+/// Kept as a `Copy` enum of fixed-size variants (plain values and function
+/// pointers, same trick [`crate::Function1DAnalytic`] uses) rather than a
+/// trait object, so `CompoundModelCoefs` can stay `Copy`.
+#[derive(Clone, Copy)]
+pub enum RateCurve {
+    /// the rate is the same at every point in the projection
+    Constant(f64),
+    /// a piecewise-linear schedule keyed by years elapsed since the start of
+    /// the projection; knots must be sorted ascending by `years_elapsed`.
+    /// querying before the first knot or after the last clamps to that
+    /// knot's rate.
+    PiecewiseLinear(&'static [(f64, f64)]),
+    /// a utilization-style rate: `base_rate + slope * utilization_at(years_elapsed)`
+    Utilization {
+        base_rate: f64,
+        slope: f64,
+        utilization_at: fn(f64) -> f64,
+    },
+}
+
+impl RateCurve {
+    /// returns the rate realized `years_elapsed` years into the projection
+    pub fn rate_at(&self, years_elapsed: f64) -> f64 {
+        match self {
+            RateCurve::Constant(rate) => *rate,
+            RateCurve::PiecewiseLinear(knots) => Self::interpolate(knots, years_elapsed),
+            RateCurve::Utilization {
+                base_rate,
+                slope,
+                utilization_at,
+            } => base_rate + slope * utilization_at(years_elapsed),
+        }
+    }
+
+    fn interpolate(knots: &[(f64, f64)], years_elapsed: f64) -> f64 {
+        match knots {
+            [] => 0.0,
+            [(_, rate)] => *rate,
+            _ => {
+                let (first_x, first_y) = knots[0];
+                if years_elapsed <= first_x {
+                    return first_y;
+                }
+                let (last_x, last_y) = knots[knots.len() - 1];
+                if years_elapsed >= last_x {
+                    return last_y;
+                }
+                for window in knots.windows(2) {
+                    let (x0, y0) = window[0];
+                    let (x1, y1) = window[1];
+                    if years_elapsed >= x0 && years_elapsed <= x1 {
+                        let fraction = (years_elapsed - x0) / (x1 - x0);
+                        return y0 + fraction * (y1 - y0);
+                    }
+                }
+                last_y
+            }
+        }
+    }
+}
+
+impl Default for RateCurve {
+    fn default() -> Self {
+        RateCurve::Constant(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_is_the_same_at_every_point() {
+        let curve = RateCurve::Constant(0.069);
+        assert_eq!(curve.rate_at(0.0), 0.069);
+        assert_eq!(curve.rate_at(100.0), 0.069);
+    }
+
+    #[test]
+    fn piecewise_linear_interpolates_between_knots() {
+        let curve = RateCurve::PiecewiseLinear(&[(0.0, 0.05), (2.0, 0.09)]);
+        assert_eq!(curve.rate_at(1.0), 0.07);
+    }
+
+    #[test]
+    fn piecewise_linear_clamps_outside_the_knot_range() {
+        let curve = RateCurve::PiecewiseLinear(&[(1.0, 0.05), (2.0, 0.09)]);
+        assert_eq!(curve.rate_at(0.0), 0.05);
+        assert_eq!(curve.rate_at(5.0), 0.09);
+    }
+
+    #[test]
+    fn piecewise_linear_with_a_single_knot_is_constant() {
+        let curve = RateCurve::PiecewiseLinear(&[(1.0, 0.05)]);
+        assert_eq!(curve.rate_at(0.0), 0.05);
+        assert_eq!(curve.rate_at(10.0), 0.05);
+    }
+
+    #[test]
+    fn utilization_combines_base_rate_and_slope() {
+        let curve = RateCurve::Utilization {
+            base_rate: 0.01,
+            slope: 0.1,
+            utilization_at: |years_elapsed| (years_elapsed / 10.0).min(1.0),
+        };
+        assert_eq!(curve.rate_at(5.0), 0.01 + 0.1 * 0.5);
+        assert_eq!(curve.rate_at(20.0), 0.01 + 0.1 * 1.0);
+    }
+}
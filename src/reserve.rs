@@ -0,0 +1,103 @@
+/// # Description
+/// Algorand enforces a minimum balance that an account can never spend
+/// below, and that minimum rises with the assets and apps the account
+/// holds. This mirrors the idea behind Solana's `rent_collector`: before
+/// treating any of an account's balance as available to compound, work out
+/// how much of it the network has actually locked up.
+/// # Comments
+/// these are the network's current minimums; see
+/// <https://developer.algorand.org/docs/get-details/accounts/#minimum-balance>
+pub const BASE_MIN_BALANCE: u64 = 100_000;
+pub const PER_ASSET_MIN_BALANCE: u64 = 100_000;
+pub const PER_APP_OPTED_IN_MIN_BALANCE: u64 = 100_000;
+pub const PER_APP_CREATED_MIN_BALANCE: u64 = 100_000;
+pub const PER_UINT_SCHEMA_MIN_BALANCE: u64 = 28_500;
+pub const PER_BYTE_SLICE_SCHEMA_MIN_BALANCE: u64 = 50_000;
+
+/// # Description
+/// the holdings that drive an account's minimum balance requirement.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccountHoldings {
+    pub num_assets_held: u64,
+    pub num_apps_opted_in: u64,
+    pub num_apps_created: u64,
+    pub created_apps_uint_schema: u64,
+    pub created_apps_byte_slice_schema: u64,
+}
+
+impl AccountHoldings {
+    /// # Description
+    /// computes the microAlgo minimum balance the network locks for these
+    /// holdings; none of this balance is ever spendable, regardless of how
+    /// the compounding math might want to use it.
+    pub fn min_balance_micro_algos(&self) -> u64 {
+        BASE_MIN_BALANCE
+            + self.num_assets_held * PER_ASSET_MIN_BALANCE
+            + self.num_apps_opted_in * PER_APP_OPTED_IN_MIN_BALANCE
+            + self.num_apps_created * PER_APP_CREATED_MIN_BALANCE
+            + self.created_apps_uint_schema * PER_UINT_SCHEMA_MIN_BALANCE
+            + self.created_apps_byte_slice_schema * PER_BYTE_SLICE_SCHEMA_MIN_BALANCE
+    }
+}
+
+/// # Description
+/// returns how much of `balance_micro_algos` is actually available to feed
+/// into `CompoundModelCoefs::initial_principal`, after setting aside the
+/// network-enforced `min_balance_micro_algos` and an extra
+/// `safety_margin_micro_algos` cushion. `None` means the account is already
+/// at or under reserve, with nothing spendable at all.
+pub fn spendable_principal(
+    balance_micro_algos: u64,
+    min_balance_micro_algos: u64,
+    safety_margin_micro_algos: u64,
+) -> Option<u64> {
+    let reserve = min_balance_micro_algos.saturating_add(safety_margin_micro_algos);
+    if balance_micro_algos <= reserve {
+        None
+    } else {
+        Some(balance_micro_algos - reserve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_balance_for_a_bare_account_is_just_the_base() {
+        let holdings = AccountHoldings::default();
+        assert_eq!(holdings.min_balance_micro_algos(), BASE_MIN_BALANCE);
+    }
+
+    #[test]
+    fn min_balance_accounts_for_every_holding_kind() {
+        let holdings = AccountHoldings {
+            num_assets_held: 2,
+            num_apps_opted_in: 1,
+            num_apps_created: 1,
+            created_apps_uint_schema: 3,
+            created_apps_byte_slice_schema: 2,
+        };
+        let expected = BASE_MIN_BALANCE
+            + 2 * PER_ASSET_MIN_BALANCE
+            + PER_APP_OPTED_IN_MIN_BALANCE
+            + PER_APP_CREATED_MIN_BALANCE
+            + 3 * PER_UINT_SCHEMA_MIN_BALANCE
+            + 2 * PER_BYTE_SLICE_SCHEMA_MIN_BALANCE;
+        assert_eq!(holdings.min_balance_micro_algos(), expected);
+    }
+
+    #[test]
+    fn spendable_principal_is_balance_minus_reserve() {
+        assert_eq!(
+            spendable_principal(1_000_000, 200_000, 100_000),
+            Some(700_000)
+        );
+    }
+
+    #[test]
+    fn spendable_principal_is_none_at_or_under_reserve() {
+        assert_eq!(spendable_principal(300_000, 200_000, 100_000), None);
+        assert_eq!(spendable_principal(250_000, 200_000, 100_000), None);
+    }
+}
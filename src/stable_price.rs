@@ -0,0 +1,106 @@
+use std::error::Error;
+
+/// # Description
+/// Smooths a noisy oracle spot price into a manipulation-resistant "stable
+/// price" that downstream compounding decisions can rely on, modeled after
+/// Mango-v4's `state::StablePriceModel`. Each [`StablePriceModel::update`]
+/// moves `stable_price` toward a fresh spot price, but the move is capped so
+/// a single transient oracle spike can't whipsaw the wait-time calculation.
+#[derive(Debug, Clone, Copy)]
+pub struct StablePriceModel {
+    pub stable_price: f64,
+    pub last_update_timestamp: u64,
+    pub delay_interval_seconds: u64,
+    pub delay_growth_limit: f64,
+    pub stable_growth_limit: f64,
+}
+
+impl StablePriceModel {
+    pub fn new(
+        initial_price: f64,
+        last_update_timestamp: u64,
+        delay_interval_seconds: u64,
+        delay_growth_limit: f64,
+        stable_growth_limit: f64,
+    ) -> Self {
+        Self {
+            stable_price: initial_price,
+            last_update_timestamp,
+            delay_interval_seconds,
+            delay_growth_limit,
+            stable_growth_limit,
+        }
+    }
+
+    /// # Description
+    /// moves `stable_price` toward `spot_price`, clamping the move to at
+    /// most `growth_limit * (elapsed / delay_interval_seconds)` in either
+    /// direction, where `growth_limit` is `delay_growth_limit` while the
+    /// price is rising and `stable_growth_limit` while it's falling. This
+    /// keeps a transient oracle spike from yanking the stable price (and
+    /// therefore the wait-time calculation) around.
+    pub fn update(&mut self, spot_price: f64, now_timestamp: u64) {
+        let elapsed = now_timestamp.saturating_sub(self.last_update_timestamp) as f64;
+        let interval_fraction = elapsed / self.delay_interval_seconds as f64;
+
+        let growth_limit = if spot_price >= self.stable_price {
+            self.delay_growth_limit
+        } else {
+            self.stable_growth_limit
+        };
+        let max_move = (growth_limit * interval_fraction).max(0.0);
+
+        let target_ratio = spot_price / self.stable_price;
+        let clamped_ratio = target_ratio.clamp(1.0 - max_move, 1.0 + max_move);
+
+        self.stable_price *= clamped_ratio;
+        self.last_update_timestamp = now_timestamp;
+    }
+}
+
+/// # Description
+/// something that can fetch the current ALGO/USD spot price from an oracle
+/// endpoint. kept as a trait so `main` can swap in whichever price feed is
+/// configured without the smoothing logic in [`StablePriceModel`] caring.
+pub trait PriceOracle {
+    fn fetch_spot_price(&self) -> Result<f64, Box<dyn Error>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+        (a - b).abs() <= epsilon
+    }
+
+    #[test]
+    fn update_moves_fully_toward_spot_price_once_the_full_interval_has_elapsed() {
+        let mut model = StablePriceModel::new(1.0, 0, 60, 0.01, 0.01);
+        model.update(1.02, 60);
+        assert!(approx_eq(model.stable_price, 1.01, 1e-9));
+    }
+
+    #[test]
+    fn update_clamps_a_rising_spike_within_a_partial_interval() {
+        let mut model = StablePriceModel::new(1.0, 0, 60, 0.01, 0.01);
+        // only half the delay interval has elapsed, so at most half of the
+        // 1% growth limit should be applied even though the spot price jumped 10%
+        model.update(1.10, 30);
+        assert!(approx_eq(model.stable_price, 1.005, 1e-9));
+    }
+
+    #[test]
+    fn update_uses_the_falling_growth_limit_when_price_drops() {
+        let mut model = StablePriceModel::new(1.0, 0, 60, 0.01, 0.02);
+        model.update(0.5, 60);
+        assert!(approx_eq(model.stable_price, 0.98, 1e-9));
+    }
+
+    #[test]
+    fn update_tracks_the_spot_price_exactly_once_it_catches_up() {
+        let mut model = StablePriceModel::new(1.0, 0, 60, 1.0, 1.0);
+        model.update(1.05, 60);
+        assert!(approx_eq(model.stable_price, 1.05, 1e-9));
+    }
+}